@@ -9,6 +9,7 @@ use futures_util::StreamExt;
 use humantime::format_duration;
 use hyper::body::Bytes;
 
+use crate::request::TlsBackend;
 use crate::results::WorkerResult;
 
 use crate::http;
@@ -23,6 +24,13 @@ pub struct BenchmarkSettings {
     pub method: Method,
     pub headers: HeaderMap,
     pub body: Bytes,
+    pub http2: bool,
+    pub streams: usize,
+    pub fail_on_error: bool,
+    pub tls_backend: TlsBackend,
+    pub insecure: bool,
+    pub rate: Option<f64>,
+    pub compression: bool,
 }
 
 pub fn start_benchmark(settings: BenchmarkSettings) {
@@ -57,6 +65,13 @@ async fn run(settings: BenchmarkSettings) -> Result<()> {
         settings.headers,
         settings.body,
         predict_size as usize,
+        settings.http2,
+        settings.streams,
+        settings.fail_on_error,
+        settings.tls_backend,
+        settings.insecure,
+        settings.rate,
+        settings.compression,
     )
     .await?;
 
@@ -78,10 +93,13 @@ async fn run(settings: BenchmarkSettings) -> Result<()> {
         return Ok(());
     }
 
+    combiner.display_connection_timing();
     combiner.display_latencies();
+    combiner.display_percentiles();
     combiner.display_requests();
     combiner.display_transfer();
 
+    combiner.display_status();
     combiner.display_errors();
 
     Ok(())
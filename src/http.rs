@@ -1,26 +1,31 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::time::Duration;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder};
 use futures_util::stream::FuturesUnordered;
-use futures_util::TryFutureExt;
+use futures_util::{StreamExt, TryFutureExt};
 use http::header::{self, HeaderMap};
-use http::{Method, Request};
+use http::{HeaderValue, Method, Request};
 use hyper::body::Bytes;
 use hyper::client::conn::{self, SendRequest};
 use hyper::Body;
-use tokio::io::{AsyncRead, AsyncWrite};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustls::ServerName;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, BufReader};
 use tokio::net::TcpStream;
-use tokio::task::JoinHandle;
+use tokio::task::{spawn_blocking, JoinHandle};
 use tokio::time::error::Elapsed;
-use tokio::time::{sleep, timeout_at, Instant};
+use tokio::time::{sleep, sleep_until, timeout_at, Instant};
 use tower::util::ServiceExt;
 use tower::Service;
 
 use crate::results::WorkerResult;
 use crate::usage::Usage;
-use crate::request::{Scheme, Request as UserRequest};
+use crate::request::{Scheme, TlsBackend, TlsConnector, Request as UserRequest};
 
 pub type Handle = JoinHandle<anyhow::Result<WorkerResult>>;
 
@@ -32,14 +37,42 @@ pub async fn start_tasks(
     headers: HeaderMap,
     body: Bytes,
     _predicted_size: usize,
+    http2: bool,
+    streams: usize,
+    fail_on_error: bool,
+    tls_backend: TlsBackend,
+    insecure: bool,
+    rate: Option<f64>,
+    compression: bool,
 ) -> anyhow::Result<FuturesUnordered<Handle>> {
     let deadline = Instant::now() + time_for;
-    let user_request = UserRequest::new(uri_string, method, headers, body).await?;
+    let user_request = UserRequest::new(
+        uri_string,
+        method,
+        headers,
+        body,
+        http2,
+        tls_backend,
+        insecure,
+    )
+    .await?;
+
+    let rate_per_connection = rate.map(|rate| rate / connections as f64);
 
     let handles = FuturesUnordered::new();
+    let benchmark_start = Instant::now();
 
     for _ in 0..connections {
-        handles.push(tokio::spawn(benchmark(deadline, user_request.clone())));
+        handles.push(tokio::spawn(benchmark(
+            deadline,
+            benchmark_start,
+            user_request.clone(),
+            http2,
+            streams,
+            fail_on_error,
+            rate_per_connection,
+            compression,
+        )));
     }
 
     Ok(handles)
@@ -47,39 +80,243 @@ pub async fn start_tasks(
 
 async fn benchmark(
     deadline: Instant,
+    benchmark_start: Instant,
     user_request: UserRequest,
+    http2: bool,
+    streams: usize,
+    fail_on_error: bool,
+    rate: Option<f64>,
+    compression: bool,
 ) -> anyhow::Result<WorkerResult> {
-    let benchmark_start = Instant::now();
     let connector = Connector::new(
         deadline,
-        user_request.addr,
-        user_request.scheme,
         user_request.host,
+        user_request.port,
+        user_request.scheme,
+        http2,
     );
 
-    let (mut send_request, mut connection_task) =
+    let (send_request, connection_task, dns_time, connect_time) =
         match timeout_at(deadline, connector.connect()).await {
             Ok(result) => result?,
             Err(_elapsed) => return Ok(WorkerResult::default()),
         };
 
+    let connection_start = Instant::now();
+
+    let mut loop_result = LoopResult::default();
+    loop_result.record_connection(dns_time, connect_time);
+
     let mut request_headers = HeaderMap::new();
     request_headers.insert(header::HOST, user_request.host_header);
     request_headers.extend(user_request.headers);
 
-    let mut request_times = Vec::new();
-    let mut error_map = HashMap::new();
+    if compression {
+        request_headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip, br"));
+    }
+
+    let result = if http2 {
+        run_http2_streams(
+            deadline,
+            send_request,
+            connection_task,
+            request_headers,
+            user_request.method,
+            user_request.uri,
+            user_request.body,
+            streams,
+            fail_on_error,
+            connection_start,
+            rate,
+            compression,
+            loop_result,
+        )
+        .await
+    } else {
+        let pacer = rate.map(|rate| Pacer::new(connection_start, rate));
+
+        run_http1_loop(
+            deadline,
+            &connector,
+            send_request,
+            connection_task,
+            request_headers,
+            user_request.method,
+            user_request.uri,
+            user_request.body,
+            fail_on_error,
+            pacer,
+            compression,
+            loop_result,
+        )
+        .await
+    };
+
+    Ok(WorkerResult {
+        total_times: vec![benchmark_start.elapsed()],
+        request_times: result.request_times,
+        buffer_sizes: vec![connector.get_received_bytes()],
+        decoded_sizes: result.decoded_sizes,
+        error_map: result.error_map,
+        status_map: result.status_map,
+        dns_times: result.dns_times,
+        connect_times: result.connect_times,
+    })
+}
+
+#[derive(Default)]
+struct LoopResult {
+    request_times: Vec<Duration>,
+    dns_times: Vec<Duration>,
+    connect_times: Vec<Duration>,
+    decoded_sizes: Vec<usize>,
+    error_map: HashMap<String, usize>,
+    status_map: HashMap<u16, usize>,
+}
+
+impl LoopResult {
+    fn record_error(&mut self, message: String) {
+        match self.error_map.get_mut(&message) {
+            Some(count) => *count += 1,
+            None => {
+                self.error_map.insert(message, 1);
+            },
+        }
+    }
+
+    fn record_status(&mut self, status: u16) {
+        match self.status_map.get_mut(&status) {
+            Some(count) => *count += 1,
+            None => {
+                self.status_map.insert(status, 1);
+            },
+        }
+    }
+
+    fn record_connection(&mut self, dns_time: Duration, connect_time: Duration) {
+        self.dns_times.push(dns_time);
+        self.connect_times.push(connect_time);
+    }
+
+    fn record_decoded_size(&mut self, size: usize) {
+        self.decoded_sizes.push(size);
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.request_times.extend(other.request_times);
+        self.dns_times.extend(other.dns_times);
+        self.connect_times.extend(other.connect_times);
+        self.decoded_sizes.extend(other.decoded_sizes);
+
+        for (message, count) in other.error_map {
+            *self.error_map.entry(message).or_insert(0) += count;
+        }
+
+        for (status, count) in other.status_map {
+            *self.status_map.entry(status).or_insert(0) += count;
+        }
+    }
+}
+
+async fn decode_body(encoding: Option<&str>, bytes: Bytes) -> anyhow::Result<Bytes> {
+    let mut decoded = Vec::new();
+
+    match encoding.map(|encoding| encoding.trim().to_ascii_lowercase()).as_deref() {
+        Some("gzip") => {
+            let reader = BufReader::new(std::io::Cursor::new(bytes));
+            GzipDecoder::new(reader).read_to_end(&mut decoded).await?;
+        },
+        Some("br") => {
+            let reader = BufReader::new(std::io::Cursor::new(bytes));
+            BrotliDecoder::new(reader).read_to_end(&mut decoded).await?;
+        },
+        _ => return Ok(bytes),
+    }
+
+    Ok(Bytes::from(decoded))
+}
+
+struct Pacer {
+    benchmark_start: Instant,
+    period: Duration,
+}
+
+impl Pacer {
+    fn new(benchmark_start: Instant, rate: f64) -> Self {
+        Self {
+            benchmark_start,
+            period: Duration::from_secs_f64(1.0 / rate),
+        }
+    }
+
+    fn expected_start(&self, index: u64) -> Instant {
+        self.benchmark_start + self.period.mul_f64(index as f64)
+    }
+
+    async fn wait_for(&self, index: u64) -> Instant {
+        let expected_start = self.expected_start(index);
+
+        if Instant::now() < expected_start {
+            sleep_until(expected_start).await;
+        }
+
+        expected_start
+    }
+}
+
+fn record_latency(result: &mut LoopResult, expected_start: Option<Instant>, actual_start: Instant) {
+    let now = Instant::now();
+
+    let latency = match expected_start {
+        Some(expected_start) if actual_start > expected_start => now.duration_since(expected_start),
+        _ => now.duration_since(actual_start),
+    };
+
+    result.request_times.push(latency);
+}
+
+async fn run_http1_loop(
+    deadline: Instant,
+    connector: &Connector,
+    mut send_request: SendRequest<Body>,
+    mut connection_task: JoinHandle<hyper::Result<()>>,
+    request_headers: HeaderMap,
+    method: Method,
+    uri: ::http::Uri,
+    body: Bytes,
+    fail_on_error: bool,
+    pacer: Option<Pacer>,
+    compression: bool,
+    mut result: LoopResult,
+) -> LoopResult {
+    let mut request_index: u64 = 0;
 
     loop {
-        let mut request = Request::new(Body::from(user_request.body.clone()));
-        *request.method_mut() = user_request.method.clone();
-        *request.uri_mut() = user_request.uri.clone();
+        let expected_start = match &pacer {
+            Some(pacer) => Some(pacer.wait_for(request_index).await),
+            None => None,
+        };
+        request_index += 1;
+
+        let mut request = Request::new(Body::from(body.clone()));
+        *request.method_mut() = method.clone();
+        *request.uri_mut() = uri.clone();
         *request.headers_mut() = request_headers.clone();
 
-        let future = send_request
-            .ready()
-            .and_then(|sr| sr.call(request))
-            .and_then(|response| hyper::body::to_bytes(response.into_body()));
+        let future = send_request.ready().and_then(|sr| sr.call(request)).and_then(
+            |response| async move {
+                let status = response.status();
+                let encoding = response
+                    .headers()
+                    .get(header::CONTENT_ENCODING)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+
+                hyper::body::to_bytes(response.into_body())
+                    .await
+                    .map(|bytes| (status, encoding, bytes))
+            },
+        );
 
         let future = async {
             tokio::select! {
@@ -90,68 +327,203 @@ async fn benchmark(
                         Err(e) => Err(anyhow!(e)),
                     }
                 },
-                result = future => result.map(|_| ()).map_err(Into::into),
+                result = future => result.map_err(Into::into),
             }
         };
 
-        let request_start = Instant::now();
+        let actual_start = Instant::now();
 
-        if let Ok(result) = timeout_at(deadline, future).await {
-            if let Err(e) = result {
-                let error = e.to_string();
+        match timeout_at(deadline, future).await {
+            Ok(Ok((status, encoding, bytes))) => {
+                result.record_status(status.as_u16());
 
-                match error_map.get_mut(&error) {
-                    Some(count) => *count += 1,
-                    None => {
-                        error_map.insert(error, 1);
-                    },
+                if fail_on_error && !status.is_success() {
+                    result.record_error(format!("HTTP {}", status.as_u16()));
+                } else {
+                    record_latency(&mut result, expected_start, actual_start);
+
+                    if compression {
+                        match decode_body(encoding.as_deref(), bytes).await {
+                            Ok(decoded) => result.record_decoded_size(decoded.len()),
+                            Err(e) => result.record_error(e.to_string()),
+                        }
+                    }
                 }
+            },
+            Ok(Err(e)) => {
+                result.record_error(e.to_string());
 
                 match connector.try_connect_until().await {
-                    Ok((sr, task)) => {
+                    Ok((sr, task, dns_time, connect_time)) => {
                         send_request = sr;
                         connection_task = task;
+                        result.record_connection(dns_time, connect_time);
                     },
                     Err(_elapsed) => break,
                 };
-            }
-        } else {
-            break;
+            },
+            Err(_elapsed) => break,
         }
+    }
 
-        request_times.push(request_start.elapsed());
+    result
+}
+
+async fn run_http2_streams(
+    deadline: Instant,
+    send_request: SendRequest<Body>,
+    connection_task: JoinHandle<hyper::Result<()>>,
+    request_headers: HeaderMap,
+    method: Method,
+    uri: ::http::Uri,
+    body: Bytes,
+    streams: usize,
+    fail_on_error: bool,
+    connection_start: Instant,
+    rate: Option<f64>,
+    compression: bool,
+    mut result: LoopResult,
+) -> LoopResult {
+    let rate_per_stream = rate.map(|rate| rate / streams.max(1) as f64);
+
+    let mut stream_tasks = FuturesUnordered::new();
+
+    for _ in 0..streams.max(1) {
+        let send_request = send_request.clone();
+        let request_headers = request_headers.clone();
+        let method = method.clone();
+        let uri = uri.clone();
+        let body = body.clone();
+        let pacer = rate_per_stream.map(|rate| Pacer::new(connection_start, rate));
+
+        stream_tasks.push(tokio::spawn(run_http2_stream(
+            deadline,
+            send_request,
+            request_headers,
+            method,
+            uri,
+            body,
+            fail_on_error,
+            pacer,
+            compression,
+        )));
     }
 
-    Ok(WorkerResult {
-        total_times: vec![benchmark_start.elapsed()],
-        request_times,
-        buffer_sizes: vec![connector.get_received_bytes()],
-        error_map,
-    })
+    while let Some(stream_result) = stream_tasks.next().await {
+        result.merge(stream_result.unwrap());
+    }
+
+    connection_task.abort();
+
+    result
 }
 
+async fn run_http2_stream(
+    deadline: Instant,
+    mut send_request: SendRequest<Body>,
+    request_headers: HeaderMap,
+    method: Method,
+    uri: ::http::Uri,
+    body: Bytes,
+    fail_on_error: bool,
+    pacer: Option<Pacer>,
+    compression: bool,
+) -> LoopResult {
+    let mut result = LoopResult::default();
+    let mut request_index: u64 = 0;
+
+    loop {
+        let expected_start = match &pacer {
+            Some(pacer) => Some(pacer.wait_for(request_index).await),
+            None => None,
+        };
+        request_index += 1;
+
+        let mut request = Request::new(Body::from(body.clone()));
+        *request.method_mut() = method.clone();
+        *request.uri_mut() = uri.clone();
+        *request.headers_mut() = request_headers.clone();
+
+        let future = send_request.ready().and_then(|sr| sr.call(request)).and_then(
+            |response| async move {
+                let status = response.status();
+                let encoding = response
+                    .headers()
+                    .get(header::CONTENT_ENCODING)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+
+                hyper::body::to_bytes(response.into_body())
+                    .await
+                    .map(|bytes| (status, encoding, bytes))
+            },
+        );
+
+        let actual_start = Instant::now();
+
+        match timeout_at(deadline, future).await {
+            Ok(Ok((status, encoding, bytes))) => {
+                result.record_status(status.as_u16());
+
+                if fail_on_error && !status.is_success() {
+                    result.record_error(format!("HTTP {}", status.as_u16()));
+                } else {
+                    record_latency(&mut result, expected_start, actual_start);
+
+                    if compression {
+                        match decode_body(encoding.as_deref(), bytes).await {
+                            Ok(decoded) => result.record_decoded_size(decoded.len()),
+                            Err(e) => result.record_error(e.to_string()),
+                        }
+                    }
+                }
+            },
+            Ok(Err(e)) => {
+                // Unlike run_http1_loop, HTTP/2 mode doesn't reconnect: the
+                // underlying connection is shared across every stream on this
+                // connection, so one stream reconnecting would orphan the
+                // `SendRequest` clones held by the others. Record the failure
+                // once and stop this stream instead of busy-spinning on an
+                // unusable connection for the rest of the benchmark.
+                result.record_error(e.to_string());
+                break;
+            },
+            Err(_elapsed) => break,
+        }
+    }
+
+    result
+}
+
+type ConnectResult = anyhow::Result<(SendRequest<Body>, JoinHandle<hyper::Result<()>>, Duration, Duration)>;
+
 struct Connector {
     deadline: Instant,
-    addr: SocketAddr,
-    scheme: Scheme,
     host: String,
+    port: u16,
+    scheme: Scheme,
     usage: Usage,
+    http2: bool,
+    rng: RefCell<StdRng>,
 }
 
 impl Connector {
-    fn new(deadline: Instant, addr: SocketAddr, scheme: Scheme, host: String) -> Self {
+    fn new(deadline: Instant, host: String, port: u16, scheme: Scheme, http2: bool) -> Self {
         Self {
             deadline,
-            addr,
-            scheme,
             host,
+            port,
+            scheme,
             usage: Usage::new(),
+            http2,
+            rng: RefCell::new(StdRng::from_entropy()),
         }
     }
 
     async fn try_connect_until(
         &self,
-    ) -> Result<(SendRequest<Body>, JoinHandle<hyper::Result<()>>), Elapsed> {
+    ) -> Result<(SendRequest<Body>, JoinHandle<hyper::Result<()>>, Duration, Duration), Elapsed>
+    {
         let future = async {
             loop {
                 if let Ok(v) = self.connect().await {
@@ -165,21 +537,66 @@ impl Connector {
         timeout_at(self.deadline, future).await
     }
 
-    async fn connect(
-        &self,
-    ) -> anyhow::Result<(SendRequest<Body>, JoinHandle<hyper::Result<()>>)> {
-        let conn_builder = conn::Builder::new();
-        let stream = self.usage.wrap_stream(TcpStream::connect(self.addr).await?);
+    async fn resolve(&self) -> anyhow::Result<(SocketAddr, Duration)> {
+        let dns_start = Instant::now();
+
+        let host = self.host.clone();
+        let port = self.port;
+        let addrs: Vec<SocketAddr> =
+            spawn_blocking(move || (host.as_str(), port).to_socket_addrs())
+                .await
+                .unwrap()
+                .context("hostname lookup failed")?
+                .collect();
+
+        let dns_time = dns_start.elapsed();
+
+        if addrs.is_empty() {
+            return Err(anyhow!("hostname lookup failed"));
+        }
 
-        let send_request = match self.scheme {
+        let idx = self.rng.borrow_mut().gen_range(0..addrs.len());
+        Ok((addrs[idx], dns_time))
+    }
+
+    async fn connect(&self) -> ConnectResult {
+        let (addr, dns_time) = self.resolve().await?;
+
+        let dial_start = Instant::now();
+
+        let mut conn_builder = conn::Builder::new();
+        conn_builder.http2_only(self.http2);
+        let stream = self.usage.wrap_stream(TcpStream::connect(addr).await?);
+
+        let (send_request, connection_task) = match self.scheme {
             Scheme::Http => handshake(conn_builder, stream).await?,
-            Scheme::Https(ref tls_connector) => {
+            Scheme::Https(TlsConnector::Native(ref tls_connector)) => {
                 let stream = tls_connector.connect(&self.host, stream).await?;
+
+                if self.http2 {
+                    if let Ok(alpn) = stream.get_ref().negotiated_alpn() {
+                        ensure_h2_negotiated(alpn.as_deref())?;
+                    }
+                }
+
+                handshake(conn_builder, stream).await?
+            },
+            Scheme::Https(TlsConnector::Rustls(ref tls_connector)) => {
+                let domain = ServerName::try_from(self.host.as_str())
+                    .map_err(|_| anyhow!("invalid DNS name for TLS: {}", self.host))?;
+                let stream = tls_connector.connect(domain, stream).await?;
+
+                if self.http2 {
+                    ensure_h2_negotiated(stream.get_ref().1.alpn_protocol())?;
+                }
+
                 handshake(conn_builder, stream).await?
             },
         };
 
-        Ok(send_request)
+        let connect_time = dial_start.elapsed();
+
+        Ok((send_request, connection_task, dns_time, connect_time))
     }
 
     fn get_received_bytes(&self) -> usize {
@@ -187,6 +604,19 @@ impl Connector {
     }
 }
 
+fn ensure_h2_negotiated(protocol: Option<&[u8]>) -> anyhow::Result<()> {
+    match protocol {
+        Some(b"h2") => Ok(()),
+        Some(other) => Err(anyhow!(
+            "server negotiated {:?} over ALPN instead of HTTP/2, but --http2 was requested",
+            String::from_utf8_lossy(other)
+        )),
+        None => Err(anyhow!(
+            "server did not negotiate HTTP/2 over ALPN, but --http2 was requested"
+        )),
+    }
+}
+
 async fn handshake<S>(
     conn_builder: conn::Builder,
     stream: S,
@@ -10,6 +10,7 @@ use humantime::parse_duration;
 use hyper::body::Bytes;
 
 use crate::bench::{self, BenchmarkSettings};
+use crate::request::TlsBackend;
 
 
 pub fn parse() -> Result<BenchmarkSettings> {
@@ -64,6 +65,39 @@ pub fn parse() -> Result<BenchmarkSettings> {
     let body: &str = args.value_of("body").unwrap_or_default();
     let body = Bytes::copy_from_slice(body.as_bytes());
 
+    let http2 = args.is_present("http2");
+    let fail_on_error = args.is_present("fail-on-error");
+
+    let streams: usize = args
+        .value_of("streams")
+        .unwrap_or("10")
+        .trim()
+        .parse()
+        .with_context(|| {
+            "invalid parameter for 'streams' given, input type must be a integer."
+        })?;
+
+    let tls_backend = match args.value_of("tls").unwrap_or("native") {
+        "native" => TlsBackend::Native,
+        "rustls" => TlsBackend::Rustls,
+        other => return Err(anyhow::anyhow!("invalid 'tls' backend '{}'", other)),
+    };
+    let insecure = args.is_present("insecure");
+
+    let rate: Option<f64> = args
+        .value_of("rate")
+        .map(|rate| rate.trim().parse())
+        .transpose()
+        .with_context(|| "invalid parameter for 'rate' given, input type must be a number.")?;
+
+    if let Some(rate) = rate {
+        if !(rate > 0.0) {
+            return Err(anyhow::anyhow!("'rate' must be greater than 0, got {}", rate));
+        }
+    }
+
+    let compression = args.is_present("compression");
+
     Ok(bench::BenchmarkSettings {
         threads,
         connections: conns,
@@ -73,6 +107,13 @@ pub fn parse() -> Result<BenchmarkSettings> {
         method,
         headers,
         body,
+        http2,
+        streams,
+        fail_on_error,
+        tls_backend,
+        insecure,
+        rate,
+        compression,
     })
 }
 
@@ -154,5 +195,59 @@ fn parse_args() -> ArgMatches<'static> {
               .takes_value(true)
               .required(false),
       )
+      .arg(
+          Arg::with_name("http2")
+              .long("http2")
+              .help("Use HTTP/2 instead of HTTP/1.1, multiplexing requests over each connection")
+              .takes_value(false)
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("fail-on-error")
+              .long("fail-on-error")
+              .help("Count non-2xx responses as errors instead of successful requests")
+              .takes_value(false)
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("streams")
+              .long("streams")
+              .short("s")
+              .help("Set the amount of concurrent in-flight streams per connection (HTTP/2 only)")
+              .takes_value(true)
+              .default_value("10")
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("tls")
+              .long("tls")
+              .help("Set the TLS backend to use for https requests")
+              .takes_value(true)
+              .possible_values(&["native", "rustls"])
+              .default_value("native")
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("insecure")
+              .long("insecure")
+              .help("Disable TLS certificate and hostname verification")
+              .takes_value(false)
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("rate")
+              .long("rate")
+              .short("R")
+              .help("Sets a constant request rate (req/s) instead of max throughput, correcting for coordinated omission")
+              .takes_value(true)
+              .required(false),
+      )
+      .arg(
+          Arg::with_name("compression")
+              .long("compression")
+              .help("Sends 'accept-encoding: gzip, br' and decodes compressed responses, reporting effective throughput alongside wire throughput")
+              .takes_value(false)
+              .required(false),
+      )
       .get_matches()
 }
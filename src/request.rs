@@ -1,15 +1,29 @@
 use std::convert::TryFrom;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use anyhow::{anyhow, Result};
 use http::header::HeaderValue;
 use http::uri::Uri;
 use http::{HeaderMap, Method};
 use hyper::body::Bytes;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
 use tokio::task::spawn_blocking;
-use tokio_native_tls::TlsConnector;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsBackend {
+    Native,
+    Rustls,
+}
+
+#[derive(Clone)]
+pub enum TlsConnector {
+    Native(tokio_native_tls::TlsConnector),
+    Rustls(tokio_rustls::TlsConnector),
+}
+
+#[derive(Clone)]
 pub enum Scheme {
     Http,
     Https(TlsConnector),
@@ -24,11 +38,67 @@ impl Scheme {
     }
 }
 
+struct NoCertVerification;
+
+impl ServerCertVerifier for NoCertVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+fn build_native_tls(alpns: &[&str], insecure: bool) -> Result<TlsConnector> {
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(insecure)
+        .danger_accept_invalid_hostnames(insecure)
+        .request_alpns(alpns)
+        .build()?;
+
+    Ok(TlsConnector::Native(tokio_native_tls::TlsConnector::from(
+        connector,
+    )))
+}
+
+fn build_rustls(alpns: &[&str], insecure: bool) -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    config.alpn_protocols = alpns.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+    if insecure {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoCertVerification));
+    }
+
+    Ok(TlsConnector::Rustls(tokio_rustls::TlsConnector::from(
+        Arc::new(config),
+    )))
+}
+
 #[derive(Clone)]
 pub struct Request {
-    pub addr: SocketAddr,
     pub scheme: Scheme,
     pub host: String,
+    pub port: u16,
     pub host_header: HeaderValue,
     pub uri: Uri,
     pub method: Method,
@@ -42,10 +112,15 @@ impl Request {
         method: Method,
         headers: HeaderMap,
         body: Bytes,
+        http2: bool,
+        tls_backend: TlsBackend,
+        insecure: bool,
     ) -> Result<Self> {
-        spawn_blocking(move || Self::blocking_new(string, method, headers, body))
-            .await
-            .unwrap()
+        spawn_blocking(move || {
+            Self::blocking_new(string, method, headers, body, http2, tls_backend, insecure)
+        })
+        .await
+        .unwrap()
     }
 
     fn blocking_new(
@@ -53,19 +128,21 @@ impl Request {
         method: Method,
         headers: HeaderMap,
         body: Bytes,
+        http2: bool,
+        tls_backend: TlsBackend,
+        insecure: bool,
     ) -> Result<Self> {
         let uri = Uri::try_from(string)?;
         let scheme = uri.scheme().unwrap_or(&http::uri::Scheme::HTTP).as_str();
 
+        let alpns: &[&str] = if http2 { &["h2", "http/1.1"] } else { &["http/1.1"] };
+
         let scheme = match scheme {
             "http" => Scheme::Http,
-            "https" => Scheme::Https(TlsConnector::from(
-                native_tls::TlsConnector::builder()
-                    .danger_accept_invalid_certs(true)
-                    .danger_accept_invalid_hostnames(true)
-                    .request_alpns(&["http/1.1"])
-                    .build()?,
-            )),
+            "https" => Scheme::Https(match tls_backend {
+                TlsBackend::Native => build_native_tls(alpns, insecure)?,
+                TlsBackend::Rustls => build_rustls(alpns, insecure)?,
+            }),
             _ => return Err(anyhow::Error::msg("invalid scheme")),
         };
         let authority = uri
@@ -82,20 +159,10 @@ impl Request {
             .unwrap_or_else(|| scheme.default_port());
         let host_header = HeaderValue::from_str(&host)?;
 
-        let addr_iter = (host.as_str(), port).to_socket_addrs()?;
-        let mut last_addr = None;
-        for addr in addr_iter {
-            last_addr = Some(addr);
-            if addr.is_ipv4() {
-                break;
-            }
-        }
-        let addr = last_addr.ok_or_else(|| anyhow!("hostname lookup failed"))?;
-
         Ok(Self {
-            addr,
             scheme,
             host,
+            port,
             host_header,
             uri,
             method,
@@ -9,8 +9,12 @@ use humansize::{format_size, DECIMAL};
 pub struct WorkerResult {
     pub total_times: Vec<Duration>,
     pub request_times: Vec<Duration>,
+    pub dns_times: Vec<Duration>,
+    pub connect_times: Vec<Duration>,
     pub buffer_sizes: Vec<usize>,
+    pub decoded_sizes: Vec<usize>,
     pub error_map: HashMap<String, usize>,
+    pub status_map: HashMap<u16, usize>,
 }
 
 impl WorkerResult {
@@ -18,15 +22,22 @@ impl WorkerResult {
         Self {
             total_times: vec![],
             request_times: vec![],
+            dns_times: vec![],
+            connect_times: vec![],
             buffer_sizes: vec![],
+            decoded_sizes: vec![],
             error_map: HashMap::new(),
+            status_map: HashMap::new(),
         }
     }
 
     pub fn combine(mut self, other: Self) -> Self {
         self.request_times.extend(other.request_times);
         self.total_times.extend(other.total_times);
+        self.dns_times.extend(other.dns_times);
+        self.connect_times.extend(other.connect_times);
         self.buffer_sizes.extend(other.buffer_sizes);
+        self.decoded_sizes.extend(other.decoded_sizes);
 
         for (message, count) in other.error_map {
             match self.error_map.get_mut(&message) {
@@ -36,6 +47,15 @@ impl WorkerResult {
                 },
             }
         }
+
+        for (status, count) in other.status_map {
+            match self.status_map.get_mut(&status) {
+                Some(c) => *c += count,
+                None => {
+                    self.status_map.insert(status, count);
+                },
+            }
+        }
         self
     }
 
@@ -51,6 +71,14 @@ impl WorkerResult {
         self.total_transfer() as f64 / self.avg_total_time().as_secs_f64()
     }
 
+    pub fn total_decoded(&self) -> usize {
+        self.decoded_sizes.iter().sum()
+    }
+
+    pub fn avg_decoded_transfer(&self) -> f64 {
+        self.total_decoded() as f64 / self.avg_total_time().as_secs_f64()
+    }
+
     pub fn avg_request_per_sec(&self) -> f64 {
         let amount = self.request_times.len() as f64;
         let avg_time = self.avg_total_time();
@@ -72,6 +100,24 @@ impl WorkerResult {
         Duration::from_secs_f64(avg / len)
     }
 
+    pub fn avg_dns_time(&self) -> Duration {
+        if self.dns_times.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let avg: f64 = self.dns_times.iter().map(|dur| dur.as_secs_f64()).sum();
+        Duration::from_secs_f64(avg / self.dns_times.len() as f64)
+    }
+
+    pub fn avg_connect_time(&self) -> Duration {
+        if self.connect_times.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let avg: f64 = self.connect_times.iter().map(|dur| dur.as_secs_f64()).sum();
+        Duration::from_secs_f64(avg / self.connect_times.len() as f64)
+    }
+
     pub fn max_request_latency(&self) -> Duration {
         self.request_times.iter().max().copied().unwrap_or_default()
     }
@@ -101,6 +147,26 @@ impl WorkerResult {
         diff.powf(0.5)
     }
 
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.request_times.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let mut sorted: Vec<f64> = self
+            .request_times
+            .iter()
+            .map(|dur| dur.as_secs_f64())
+            .collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = sorted.len();
+        let idx = (((p / 100.0) * n as f64).ceil() as usize)
+            .saturating_sub(1)
+            .min(n - 1);
+
+        Duration::from_secs_f64(sorted[idx])
+    }
+
     pub fn display_latencies(&mut self) {
         let modified = 1000_f64;
         let avg = self.avg_request_latency().as_secs_f64() * modified;
@@ -126,6 +192,42 @@ impl WorkerResult {
         );
     }
 
+    pub fn display_percentiles(&self) {
+        let modified = 1000_f64;
+
+        println!(
+            "{:<13} {:<7} {:<7} {:<7} {:<7} {:<7}",
+            "Latency".bold(),
+            "50%",
+            "75%",
+            "90%",
+            "99%",
+            "99.9%",
+        );
+        println!(
+            "{:<13} {:<7} {:<7} {:<7} {:<7} {:<7}\n",
+            "Distribution",
+            format!("{:.2}ms", self.percentile(50.0).as_secs_f64() * modified),
+            format!("{:.2}ms", self.percentile(75.0).as_secs_f64() * modified),
+            format!("{:.2}ms", self.percentile(90.0).as_secs_f64() * modified),
+            format!("{:.2}ms", self.percentile(99.0).as_secs_f64() * modified),
+            format!("{:.2}ms", self.percentile(99.9).as_secs_f64() * modified),
+        );
+    }
+
+    pub fn display_connection_timing(&self) {
+        let modified = 1000_f64;
+
+        println!(
+            "{:<13} {:<7}",
+            "DNS Lookup", format!("{:.2}ms", self.avg_dns_time().as_secs_f64() * modified),
+        );
+        println!(
+            "{:<13} {:<7}\n",
+            "Connect", format!("{:.2}ms", self.avg_connect_time().as_secs_f64() * modified),
+        );
+    }
+
     pub fn display_requests(&mut self) {
         let total = self.total_requests();
         let avg = self.avg_request_per_sec();
@@ -148,7 +250,21 @@ impl WorkerResult {
             "Transfer: {:<15} Total: {:<7} ",
             format!("{}/s", display_rate).as_str().blue().bold(),
             display_total.as_str(),
-        )
+        );
+
+        if !self.decoded_sizes.is_empty() {
+            let decoded_total = self.total_decoded() as f64;
+            let decoded_rate = self.avg_decoded_transfer();
+
+            let display_decoded_total = format_size(decoded_total as u64, DECIMAL);
+            let display_decoded_rate = format_size(decoded_rate as u64, DECIMAL);
+
+            println!(
+                "Effective: {:<14} Total: {:<7} ",
+                format!("{}/s", display_decoded_rate).as_str().blue().bold(),
+                display_decoded_total.as_str(),
+            )
+        }
     }
 
     pub fn display_errors(&self) {
@@ -161,4 +277,31 @@ impl WorkerResult {
         }
     }
 
+    pub fn display_status(&self) {
+        if self.status_map.is_empty() {
+            return;
+        }
+
+        let mut classes: HashMap<u16, usize> = HashMap::new();
+        for (status, count) in &self.status_map {
+            *classes.entry(status / 100).or_insert(0) += count;
+        }
+
+        println!();
+        println!("Status codes:");
+
+        for class in [2_u16, 3, 4, 5] {
+            if let Some(count) = classes.get(&class) {
+                println!("  {}xx: {}", class, count);
+            }
+        }
+
+        let mut codes: Vec<_> = self.status_map.iter().collect();
+        codes.sort_by_key(|(status, _)| **status);
+
+        for (status, count) in codes {
+            println!("    {} {}", status, count);
+        }
+    }
+
 }